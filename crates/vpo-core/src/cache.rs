@@ -0,0 +1,344 @@
+use crate::hasher::compute_partial_file_hash;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// On-disk cache format version. Bump this whenever the record layout
+/// changes; [`read_cache`] rebuilds transparently on a mismatch instead of
+/// trying to parse a layout it doesn't understand.
+const CACHE_VERSION: u8 = 1;
+
+/// Upper bound on a single length-prefixed field (path or hash) read from
+/// the cache file. Real paths and hex hashes are nowhere near this size;
+/// the limit exists so a corrupt or truncated length prefix can't make us
+/// try to allocate gigabytes before [`read_record`] has a chance to fail.
+const MAX_RECORD_FIELD_LEN: usize = 64 * 1024;
+
+/// A single cached hash, keyed externally on the file path it describes.
+#[derive(Clone)]
+struct CacheRecord {
+    size: u64,
+    mtime: u64,
+    hash: String,
+}
+
+/// Hash result for a single file, plus whether it was served from the cache.
+#[derive(Clone)]
+pub struct CachedFileHash {
+    pub path: String,
+    pub hash: Option<String>,
+    pub error: Option<String>,
+    pub cached: bool,
+}
+
+impl IntoPy<PyObject> for CachedFileHash {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("path", self.path).unwrap();
+        dict.set_item("hash", self.hash).unwrap();
+        dict.set_item("error", self.error).unwrap();
+        dict.set_item("cached", self.cached).unwrap();
+        dict.into()
+    }
+}
+
+/// Truncate a file's mtime to whole seconds, matching the precision stored
+/// on disk.
+fn mtime_secs(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Read one `(path, record)` pair from the cache stream, or `None` at a
+/// clean end-of-file.
+fn read_record(reader: &mut impl Read) -> io::Result<Option<(String, CacheRecord)>> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let path_len = u32::from_le_bytes(len_buf) as usize;
+    if path_len > MAX_RECORD_FIELD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("cache record path length {path_len} exceeds maximum"),
+        ));
+    }
+    let mut path_buf = vec![0u8; path_len];
+    reader.read_exact(&mut path_buf)?;
+    let path = String::from_utf8_lossy(&path_buf).into_owned();
+
+    let mut size_buf = [0u8; 8];
+    reader.read_exact(&mut size_buf)?;
+    let size = u64::from_le_bytes(size_buf);
+
+    let mut mtime_buf = [0u8; 8];
+    reader.read_exact(&mut mtime_buf)?;
+    let mtime = u64::from_le_bytes(mtime_buf);
+
+    let mut hash_len_buf = [0u8; 4];
+    reader.read_exact(&mut hash_len_buf)?;
+    let hash_len = u32::from_le_bytes(hash_len_buf) as usize;
+    if hash_len > MAX_RECORD_FIELD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("cache record hash length {hash_len} exceeds maximum"),
+        ));
+    }
+    let mut hash_buf = vec![0u8; hash_len];
+    reader.read_exact(&mut hash_buf)?;
+    let hash = String::from_utf8_lossy(&hash_buf).into_owned();
+
+    Ok(Some((path, CacheRecord { size, mtime, hash })))
+}
+
+fn write_record(writer: &mut impl Write, path: &str, record: &CacheRecord) -> io::Result<()> {
+    writer.write_all(&(path.len() as u32).to_le_bytes())?;
+    writer.write_all(path.as_bytes())?;
+    writer.write_all(&record.size.to_le_bytes())?;
+    writer.write_all(&record.mtime.to_le_bytes())?;
+    writer.write_all(&(record.hash.len() as u32).to_le_bytes())?;
+    writer.write_all(record.hash.as_bytes())?;
+    Ok(())
+}
+
+/// Load the cache from disk, keyed on path. A missing file or a version
+/// mismatch falls back to an empty cache rather than erroring; a
+/// truncated/corrupt record ends the read, keeping the records before it.
+/// The next write rebuilds the file from what was loaded.
+fn read_cache(path: &Path) -> HashMap<String, CacheRecord> {
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return HashMap::new(),
+    };
+
+    let mut version = [0u8; 1];
+    if file.read_exact(&mut version).is_err() || version[0] != CACHE_VERSION {
+        return HashMap::new();
+    }
+
+    let mut entries = HashMap::new();
+    loop {
+        match read_record(&mut file) {
+            Ok(Some((path, record))) => {
+                entries.insert(path, record);
+            }
+            Ok(None) => break,
+            Err(_) => break,
+        }
+    }
+    entries
+}
+
+/// Write the cache to disk atomically: the new contents land in a temp
+/// file next to `path`, which is then renamed into place so a reader never
+/// observes a half-written cache.
+fn write_cache(path: &Path, entries: &HashMap<String, CacheRecord>) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&[CACHE_VERSION])?;
+        for (path, record) in entries {
+            write_record(&mut file, path, record)?;
+        }
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// Hash multiple files, consulting an on-disk cache keyed on `(path, size,
+/// mtime)` so unchanged files are served without reading their contents.
+///
+/// Args:
+///     paths: List of file paths to hash
+///     cache_path: Path to the on-disk cache file (created if missing)
+///
+/// Returns:
+///     List of dicts with path, hash (or None), error (or None), and a
+///     `cached` flag for each file
+#[pyfunction]
+pub fn hash_files_cached(paths: Vec<String>, cache_path: String) -> Vec<CachedFileHash> {
+    let cache_file = PathBuf::from(&cache_path);
+    let cache = read_cache(&cache_file);
+
+    let results: Vec<(CachedFileHash, Option<(String, CacheRecord)>)> = paths
+        .par_iter()
+        .map(|path| {
+            let metadata = match fs::metadata(path) {
+                Ok(m) => m,
+                Err(e) => {
+                    return (
+                        CachedFileHash {
+                            path: path.clone(),
+                            hash: None,
+                            error: Some(e.to_string()),
+                            cached: false,
+                        },
+                        None,
+                    )
+                }
+            };
+            let size = metadata.len();
+            let mtime = mtime_secs(&metadata);
+
+            if let Some(record) = cache.get(path) {
+                if record.size == size && record.mtime == mtime {
+                    return (
+                        CachedFileHash {
+                            path: path.clone(),
+                            hash: Some(record.hash.clone()),
+                            error: None,
+                            cached: true,
+                        },
+                        None,
+                    );
+                }
+            }
+
+            match compute_partial_file_hash(path) {
+                Ok(hash) => (
+                    CachedFileHash {
+                        path: path.clone(),
+                        hash: Some(hash.clone()),
+                        error: None,
+                        cached: false,
+                    },
+                    Some((path.clone(), CacheRecord { size, mtime, hash })),
+                ),
+                Err(e) => (
+                    CachedFileHash {
+                        path: path.clone(),
+                        hash: None,
+                        error: Some(e),
+                        cached: false,
+                    },
+                    None,
+                ),
+            }
+        })
+        .collect();
+
+    let mut updated_cache = cache;
+    let mut dirty = false;
+    for (_, update) in &results {
+        if let Some((path, record)) = update {
+            updated_cache.insert(path.clone(), record.clone());
+            dirty = true;
+        }
+    }
+
+    if dirty {
+        // Best-effort: a failed cache write shouldn't fail the hash pass,
+        // it just means the next run warms up from scratch again.
+        let _ = write_cache(&cache_file, &updated_cache);
+    }
+
+    results.into_iter().map(|(result, _)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_cache_miss_then_hit() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("video.bin");
+        let cache_path = dir.path().join("cache.bin");
+        fs::write(&file_path, b"hello world").unwrap();
+
+        let first = hash_files_cached(
+            vec![file_path.to_string_lossy().to_string()],
+            cache_path.to_string_lossy().to_string(),
+        );
+        assert_eq!(first.len(), 1);
+        assert!(!first[0].cached);
+        assert!(first[0].hash.is_some());
+
+        let second = hash_files_cached(
+            vec![file_path.to_string_lossy().to_string()],
+            cache_path.to_string_lossy().to_string(),
+        );
+        assert_eq!(second.len(), 1);
+        assert!(second[0].cached);
+        assert_eq!(second[0].hash, first[0].hash);
+    }
+
+    #[test]
+    fn test_cache_invalidated_on_modification() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("video.bin");
+        let cache_path = dir.path().join("cache.bin");
+        fs::write(&file_path, b"version one").unwrap();
+
+        let first = hash_files_cached(
+            vec![file_path.to_string_lossy().to_string()],
+            cache_path.to_string_lossy().to_string(),
+        );
+
+        // Force a distinct mtime, then change the contents.
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        fs::write(&file_path, b"a very different version two").unwrap();
+
+        let second = hash_files_cached(
+            vec![file_path.to_string_lossy().to_string()],
+            cache_path.to_string_lossy().to_string(),
+        );
+        assert!(!second[0].cached);
+        assert_ne!(second[0].hash, first[0].hash);
+    }
+
+    #[test]
+    fn test_unreadable_cache_file_rebuilds() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("video.bin");
+        let cache_path = dir.path().join("cache.bin");
+        fs::write(&file_path, b"hello").unwrap();
+        fs::write(&cache_path, b"not a real cache").unwrap();
+
+        let result = hash_files_cached(
+            vec![file_path.to_string_lossy().to_string()],
+            cache_path.to_string_lossy().to_string(),
+        );
+        assert_eq!(result.len(), 1);
+        assert!(result[0].hash.is_some());
+    }
+
+    #[test]
+    fn test_read_record_rejects_oversized_length_prefix() {
+        // A corrupt or truncated length prefix can claim an absurd field
+        // size; read_record must refuse to allocate for it instead of
+        // trying to read gigabytes into memory.
+        let mut bogus = Vec::new();
+        bogus.extend_from_slice(&(u32::MAX).to_le_bytes());
+        let mut cursor = io::Cursor::new(bogus);
+        let err = match read_record(&mut cursor) {
+            Err(e) => e,
+            Ok(_) => panic!("expected an error for an oversized length prefix"),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_read_cache_falls_back_on_oversized_length_prefix() {
+        let dir = tempdir().unwrap();
+        let cache_path = dir.path().join("cache.bin");
+        let mut bogus = vec![CACHE_VERSION];
+        bogus.extend_from_slice(&(u32::MAX).to_le_bytes());
+        fs::write(&cache_path, &bogus).unwrap();
+
+        let entries = read_cache(&cache_path);
+        assert!(entries.is_empty());
+    }
+}
@@ -1,10 +1,91 @@
+use crate::progress::{CancellationToken, ProgressReporter};
+use globset::{Glob, GlobMatcher};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
-use rayon::prelude::*;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+const IGNORE_FILE_NAME: &str = ".vpoignore";
+
+/// Rewrite a bare pattern (no `/` and no glob metacharacters) into the set
+/// of anchored patterns that reproduce `.gitignore` semantics for it:
+/// `Sample` should exclude a file or directory named `Sample` anywhere
+/// under the scan root, plus everything beneath it if it's a directory.
+/// Patterns that already contain a `/` or glob metacharacters are left
+/// untouched, since the caller already scoped them.
+fn anchor_bare_pattern(pattern: &str) -> Vec<String> {
+    let is_bare = !pattern.contains(['/', '*', '?', '[', '{']);
+    if is_bare {
+        vec![format!("**/{pattern}"), format!("**/{pattern}/**")]
+    } else {
+        vec![pattern.to_string()]
+    }
+}
+
+/// A compiled, ordered set of gitignore-style exclude patterns.
+///
+/// Patterns are matched against a path relative to the scan root, in the
+/// order they were given. Like `.gitignore`, later patterns override
+/// earlier ones, and a pattern prefixed with `!` re-includes a path an
+/// earlier pattern excluded. A bare pattern with no `/` or glob
+/// metacharacters (e.g. `Sample`) is auto-anchored to match at any depth,
+/// matching `.gitignore` semantics, rather than only at the scan root.
+struct ExcludeMatcher {
+    patterns: Vec<(GlobMatcher, bool)>,
+}
+
+impl ExcludeMatcher {
+    fn compile(patterns: &[String]) -> Result<Self, String> {
+        let mut compiled = Vec::new();
+        for raw in patterns {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negate, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            for anchored in anchor_bare_pattern(pattern) {
+                let matcher = Glob::new(&anchored)
+                    .map_err(|e| format!("invalid exclude pattern '{}': {}", pattern, e))?
+                    .compile_matcher();
+                compiled.push((matcher, negate));
+            }
+        }
+        Ok(ExcludeMatcher { patterns: compiled })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether `relative_path` is excluded, applying patterns in order so
+    /// the last one to match a path wins.
+    fn is_excluded(&self, relative_path: &Path) -> bool {
+        let mut excluded = false;
+        for (matcher, negate) in &self.patterns {
+            if matcher.is_match(relative_path) {
+                excluded = !negate;
+            }
+        }
+        excluded
+    }
+}
+
+/// Read additional exclude patterns from a `.vpoignore` file at the scan
+/// root, if one exists. Missing files are not an error: there simply are
+/// no extra patterns.
+fn read_vpoignore(root: &Path) -> Vec<String> {
+    let ignore_path = root.join(IGNORE_FILE_NAME);
+    match fs::read_to_string(&ignore_path) {
+        Ok(contents) => contents.lines().map(|l| l.to_string()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
 /// Discovered file information returned to Python.
 #[derive(Clone)]
 pub struct DiscoveredFile {
@@ -23,22 +104,96 @@ impl IntoPy<PyObject> for DiscoveredFile {
     }
 }
 
+/// Outcome of a [`discover_videos`] pass: the files found before it
+/// stopped, plus whether it stopped early because `cancel_token` was
+/// cancelled.
+#[derive(Clone)]
+pub struct DiscoverResult {
+    pub files: Vec<DiscoveredFile>,
+    pub cancelled: bool,
+}
+
+impl IntoPy<PyObject> for DiscoverResult {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("files", self.files.into_py(py)).unwrap();
+        dict.set_item("cancelled", self.cancelled).unwrap();
+        dict.into()
+    }
+}
+
 /// Recursively discover video files in a directory.
 ///
 /// Args:
 ///     root_path: The root directory to scan
 ///     extensions: List of file extensions to match (e.g., ["mkv", "mp4"])
 ///     follow_symlinks: Whether to follow symbolic links
+///     exclude_patterns: Gitignore-style glob patterns (e.g. "**/Sample/*",
+///         "*-trailer.mkv") matched against each entry's path relative to
+///         `root_path`; a pattern prefixed with `!` re-includes a path an
+///         earlier pattern excluded. A `.vpoignore` file at `root_path`, if
+///         present, contributes additional patterns the same way, with `#`
+///         starting a comment line.
+///     progress_callback: Optional callable invoked periodically with the
+///         number of files discovered so far
+///     cancel_token: Optional `CancellationToken`; when cancelled, the
+///         directory walk stops visiting new entries and the scan returns
+///         whatever it has found so far with `cancelled` set to `True`
 ///
 /// Returns:
-///     List of dicts with path, size, and modified timestamp for each file
+///     A dict with `files` (the usual list of path/size/modified dicts) and
+///     a `cancelled` flag
 #[pyfunction]
-#[pyo3(signature = (root_path, extensions, follow_symlinks = false))]
+#[pyo3(signature = (
+    root_path,
+    extensions,
+    follow_symlinks = false,
+    exclude_patterns = vec![],
+    progress_callback = None,
+    cancel_token = None
+))]
 pub fn discover_videos(
     root_path: &str,
     extensions: Vec<String>,
     follow_symlinks: bool,
-) -> PyResult<Vec<DiscoveredFile>> {
+    exclude_patterns: Vec<String>,
+    progress_callback: Option<PyObject>,
+    cancel_token: Option<Py<CancellationToken>>,
+) -> PyResult<DiscoverResult> {
+    let cancel_flag = cancel_token.map(|token| Python::with_gil(|py| token.borrow(py).clone()));
+    if progress_callback.is_none() && cancel_flag.is_none() {
+        return discover_videos_with_cancellation(
+            root_path,
+            extensions,
+            follow_symlinks,
+            exclude_patterns,
+            None,
+            None,
+        );
+    }
+    // Release the GIL for the walk so another Python thread can call `cancel()`.
+    Python::with_gil(|py| {
+        py.allow_threads(|| {
+            discover_videos_with_cancellation(
+                root_path,
+                extensions,
+                follow_symlinks,
+                exclude_patterns,
+                progress_callback,
+                cancel_flag,
+            )
+        })
+    })
+}
+
+fn discover_videos_with_cancellation(
+    root_path: &str,
+    extensions: Vec<String>,
+    follow_symlinks: bool,
+    exclude_patterns: Vec<String>,
+    progress_callback: Option<PyObject>,
+    cancel_flag: Option<CancellationToken>,
+) -> PyResult<DiscoverResult> {
     let extensions: HashSet<String> = extensions.into_iter().map(|e| e.to_lowercase()).collect();
     let root = PathBuf::from(root_path);
 
@@ -55,75 +210,103 @@ pub fn discover_videos(
         ));
     }
 
+    let mut all_patterns = exclude_patterns;
+    all_patterns.extend(read_vpoignore(&root));
+    let excludes = ExcludeMatcher::compile(&all_patterns)
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+
     // Track visited directories to detect symlink cycles
     let visited: std::sync::Mutex<HashSet<PathBuf>> = std::sync::Mutex::new(HashSet::new());
 
     let walker = WalkDir::new(&root).follow_links(follow_symlinks);
+    let reporter = ProgressReporter::new(progress_callback);
 
-    // Collect all entries first
-    let entries: Vec<_> = walker
-        .into_iter()
-        .filter_entry(|e| {
-            // Skip hidden directories
-            if e.file_type().is_dir() {
-                if let Some(name) = e.file_name().to_str() {
-                    if name.starts_with('.') && e.depth() > 0 {
-                        return false;
-                    }
+    // Walk the tree in a plain loop rather than collecting it into a `Vec`
+    // first: on a library with a terabyte-scale directory tree, the
+    // readdir/stat traversal itself is the dominant cost, so `cancel_flag`
+    // has to be polled (and `reporter` ticked) as entries are visited, not
+    // only once a later parallel pass starts over an already-complete list.
+    let mut files = Vec::new();
+    let mut cancelled = false;
+    for entry in walker.into_iter().filter_entry(|e| {
+        // Skip hidden directories
+        if e.file_type().is_dir() {
+            if let Some(name) = e.file_name().to_str() {
+                if name.starts_with('.') && e.depth() > 0 {
+                    return false;
                 }
             }
+        }
 
-            // Symlink cycle detection
-            if follow_symlinks && e.file_type().is_dir() {
-                if let Ok(canonical) = e.path().canonicalize() {
-                    let mut visited_guard = visited.lock().unwrap();
-                    if visited_guard.contains(&canonical) {
-                        return false; // Skip cycle
-                    }
-                    visited_guard.insert(canonical);
+        // Prune whole subtrees matched by an exclude pattern, rather
+        // than filtering individual files out after the fact.
+        if !excludes.is_empty() {
+            if let Ok(relative) = e.path().strip_prefix(&root) {
+                if e.depth() > 0 && excludes.is_excluded(relative) {
+                    return false;
                 }
             }
+        }
 
-            true
-        })
-        .filter_map(|e| e.ok())
-        .collect();
-
-    // Process files in parallel
-    let files: Vec<DiscoveredFile> = entries
-        .par_iter()
-        .filter_map(|entry| {
-            if !entry.file_type().is_file() {
-                return None;
+        // Symlink cycle detection
+        if follow_symlinks && e.file_type().is_dir() {
+            if let Ok(canonical) = e.path().canonicalize() {
+                let mut visited_guard = visited.lock().unwrap();
+                if visited_guard.contains(&canonical) {
+                    return false; // Skip cycle
+                }
+                visited_guard.insert(canonical);
             }
+        }
 
-            let path = entry.path();
-            let extension = path
-                .extension()
-                .and_then(|e| e.to_str())
-                .map(|e| e.to_lowercase())?;
+        true
+    }) {
+        if cancel_flag.as_ref().is_some_and(CancellationToken::is_set) {
+            cancelled = true;
+            break;
+        }
 
-            if !extensions.contains(&extension) {
-                return None;
-            }
+        let Ok(entry) = entry else {
+            continue;
+        };
 
-            let metadata = entry.metadata().ok()?;
-            let modified = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs_f64())
-                .unwrap_or(0.0);
-
-            Some(DiscoveredFile {
-                path: path.to_string_lossy().to_string(),
-                size: metadata.len(),
-                modified,
-            })
-        })
-        .collect();
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        let Some(extension) = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+        else {
+            continue;
+        };
+
+        if !extensions.contains(&extension) {
+            continue;
+        }
+
+        reporter.tick();
 
-    Ok(files)
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+
+        files.push(DiscoveredFile {
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified,
+        });
+    }
+
+    Ok(DiscoverResult { files, cancelled })
 }
 
 #[cfg(test)]
@@ -135,9 +318,16 @@ mod tests {
     #[test]
     fn test_discover_videos_empty_dir() {
         let dir = tempdir().unwrap();
-        let result = discover_videos(dir.path().to_str().unwrap(), vec!["mkv".to_string()], false);
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec![],
+            None,
+            None,
+        );
         assert!(result.is_ok());
-        assert!(result.unwrap().is_empty());
+        assert!(result.unwrap().files.is_empty());
     }
 
     #[test]
@@ -151,9 +341,12 @@ mod tests {
             dir.path().to_str().unwrap(),
             vec!["mkv".to_string(), "mp4".to_string()],
             false,
+            vec![],
+            None,
+            None,
         );
         assert!(result.is_ok());
-        let files = result.unwrap();
+        let files = result.unwrap().files;
         assert_eq!(files.len(), 2);
     }
 
@@ -163,9 +356,16 @@ mod tests {
         fs::create_dir(dir.path().join("nested")).unwrap();
         File::create(dir.path().join("nested/deep.mkv")).unwrap();
 
-        let result = discover_videos(dir.path().to_str().unwrap(), vec!["mkv".to_string()], false);
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec![],
+            None,
+            None,
+        );
         assert!(result.is_ok());
-        let files = result.unwrap();
+        let files = result.unwrap().files;
         assert_eq!(files.len(), 1);
     }
 
@@ -176,16 +376,158 @@ mod tests {
         File::create(dir.path().join(".hidden/video.mkv")).unwrap();
         File::create(dir.path().join("visible.mkv")).unwrap();
 
-        let result = discover_videos(dir.path().to_str().unwrap(), vec!["mkv".to_string()], false);
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec![],
+            None,
+            None,
+        );
         assert!(result.is_ok());
-        let files = result.unwrap();
+        let files = result.unwrap().files;
         assert_eq!(files.len(), 1);
         assert!(files[0].path.contains("visible"));
     }
 
     #[test]
     fn test_discover_videos_not_found() {
-        let result = discover_videos("/nonexistent/path", vec!["mkv".to_string()], false);
+        let result = discover_videos(
+            "/nonexistent/path",
+            vec!["mkv".to_string()],
+            false,
+            vec![],
+            None,
+            None,
+        );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_discover_videos_cancelled_upfront_returns_no_files() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("video.mkv")).unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let result = discover_videos_with_cancellation(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec![],
+            None,
+            Some(token),
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.cancelled);
+        assert!(result.files.is_empty());
+    }
+
+    #[test]
+    fn test_discover_videos_exclude_pattern_prunes_subtree() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Sample")).unwrap();
+        File::create(dir.path().join("Sample/teaser.mkv")).unwrap();
+        File::create(dir.path().join("movie.mkv")).unwrap();
+
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec!["**/Sample/*".to_string()],
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let files = result.unwrap().files;
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.contains("movie"));
+    }
+
+    #[test]
+    fn test_discover_videos_exclude_pattern_by_suffix() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("movie-trailer.mkv")).unwrap();
+        File::create(dir.path().join("movie.mkv")).unwrap();
+
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec!["*-trailer.mkv".to_string()],
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let files = result.unwrap().files;
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.contains("movie.mkv"));
+    }
+
+    #[test]
+    fn test_discover_videos_bare_pattern_matches_nested_path() {
+        // A bare pattern like "Sample" should behave like .gitignore and
+        // exclude a directory of that name no matter how deep it's nested,
+        // not just one sitting directly under the scan root.
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Movie/Sample")).unwrap();
+        File::create(dir.path().join("Movie/Sample/teaser.mkv")).unwrap();
+        File::create(dir.path().join("Movie/movie.mkv")).unwrap();
+
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec!["Sample".to_string()],
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let files = result.unwrap().files;
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.contains("movie.mkv"));
+    }
+
+    #[test]
+    fn test_discover_videos_reads_vpoignore_file() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Extras")).unwrap();
+        File::create(dir.path().join("Extras/bonus.mkv")).unwrap();
+        File::create(dir.path().join("movie.mkv")).unwrap();
+        fs::write(dir.path().join(".vpoignore"), "# comment\n**/Extras/*\n").unwrap();
+
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec![],
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let files = result.unwrap().files;
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.contains("movie"));
+    }
+
+    #[test]
+    fn test_discover_videos_negated_pattern_reincludes() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Sample")).unwrap();
+        File::create(dir.path().join("Sample/keep.mkv")).unwrap();
+
+        let result = discover_videos(
+            dir.path().to_str().unwrap(),
+            vec!["mkv".to_string()],
+            false,
+            vec!["**/Sample/*".to_string(), "!**/Sample/keep.mkv".to_string()],
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let files = result.unwrap().files;
+        assert_eq!(files.len(), 1);
+    }
 }
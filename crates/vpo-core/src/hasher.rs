@@ -1,12 +1,25 @@
+use crate::progress::{CancellationToken, ProgressReporter};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
-use xxhash_rust::xxh64::xxh64;
+use xxhash_rust::xxh64::{xxh64, Xxh64};
 
 const CHUNK_SIZE: usize = 65536; // 64KB
 
+/// Which of the two fingerprinting strategies to run.
+///
+/// `Partial` is the cheap "first 64KB + last 64KB + size" fingerprint used
+/// for a first pass over a whole library. `Full` streams the entire file
+/// and should only be used to confirm collisions `Partial` turns up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashMode {
+    Partial,
+    Full,
+}
+
 /// Hash result for a single file.
 #[derive(Clone)]
 pub struct FileHash {
@@ -25,13 +38,38 @@ impl IntoPy<PyObject> for FileHash {
     }
 }
 
+/// Outcome of a [`hash_files`] pass: the hashes computed before it stopped,
+/// plus whether it stopped early because `cancel_token` was cancelled.
+#[derive(Clone)]
+pub struct HashBatchResult {
+    pub results: Vec<FileHash>,
+    pub cancelled: bool,
+}
+
+impl IntoPy<PyObject> for HashBatchResult {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("results", self.results.into_py(py)).unwrap();
+        dict.set_item("cancelled", self.cancelled).unwrap();
+        dict.into()
+    }
+}
+
+/// Compute a hash of a file using the given [`HashMode`].
+fn compute_hash_with_mode(path: &str, mode: HashMode) -> Result<String, String> {
+    match mode {
+        HashMode::Partial => compute_partial_file_hash(path),
+        HashMode::Full => compute_full_file_hash(path),
+    }
+}
+
 /// Compute a partial hash of a file using xxHash64.
 ///
 /// For files >= 128KB: hash first 64KB + last 64KB + file size
 /// For smaller files: hash the entire file
 ///
 /// Returns hash in format: xxh64:<first_hash>:<last_hash>:<size>
-fn compute_file_hash(path: &str) -> Result<String, String> {
+pub(crate) fn compute_partial_file_hash(path: &str) -> Result<String, String> {
     let mut file = File::open(path).map_err(|e| e.to_string())?;
     let metadata = file.metadata().map_err(|e| e.to_string())?;
     let size = metadata.len();
@@ -63,28 +101,150 @@ fn compute_file_hash(path: &str) -> Result<String, String> {
     }
 }
 
+/// Compute a full hash of a file using streaming xxHash64.
+///
+/// The whole file is read through in `CHUNK_SIZE` blocks and fed into the
+/// streaming `Xxh64` state, so memory use stays flat regardless of file
+/// size. Use this to confirm a collision found by [`compute_partial_file_hash`];
+/// it is too slow to run as a first pass over a whole library.
+///
+/// Returns hash in format: xxh64-full:<hash>:<size>
+fn compute_full_file_hash(path: &str) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let metadata = file.metadata().map_err(|e| e.to_string())?;
+    let size = metadata.len();
+
+    let mut hasher = Xxh64::new(0);
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("xxh64-full:{:016x}:{}", hasher.digest(), size))
+}
+
 /// Hash multiple files in parallel.
 ///
 /// Args:
 ///     paths: List of file paths to hash
+///     progress_callback: Optional callable invoked periodically with the
+///         number of files hashed so far
+///     cancel_token: Optional `CancellationToken`; when cancelled, the
+///         rayon loop stops starting new work and the pass returns whatever
+///         it has hashed so far with `cancelled` set to `True`
+///
+/// Returns:
+///     A dict with `results` (the usual list of path/hash/error dicts) and
+///     a `cancelled` flag
+#[pyfunction]
+#[pyo3(signature = (paths, progress_callback = None, cancel_token = None))]
+pub fn hash_files(
+    paths: Vec<String>,
+    progress_callback: Option<PyObject>,
+    cancel_token: Option<Py<CancellationToken>>,
+) -> HashBatchResult {
+    let cancel_flag = cancel_token.map(|token| Python::with_gil(|py| token.borrow(py).clone()));
+    if progress_callback.is_none() && cancel_flag.is_none() {
+        return hash_files_with_cancellation(paths, None, None);
+    }
+    // Release the GIL for the pass: the rayon workers take it to invoke the
+    // progress callback, and another Python thread needs it to call `cancel()`.
+    Python::with_gil(|py| {
+        py.allow_threads(|| hash_files_with_cancellation(paths, progress_callback, cancel_flag))
+    })
+}
+
+fn hash_files_with_cancellation(
+    paths: Vec<String>,
+    progress_callback: Option<PyObject>,
+    cancel_flag: Option<CancellationToken>,
+) -> HashBatchResult {
+    let reporter = ProgressReporter::new(progress_callback);
+
+    let results: Vec<FileHash> = paths
+        .par_iter()
+        .filter_map(|path| {
+            if cancel_flag.as_ref().is_some_and(CancellationToken::is_set) {
+                return None;
+            }
+            reporter.tick();
+            Some(match compute_partial_file_hash(path) {
+                Ok(hash) => FileHash {
+                    path: path.clone(),
+                    hash: Some(hash),
+                    error: None,
+                },
+                Err(e) => FileHash {
+                    path: path.clone(),
+                    hash: None,
+                    error: Some(e),
+                },
+            })
+        })
+        .collect();
+
+    let cancelled = cancel_flag.as_ref().is_some_and(CancellationToken::is_set);
+    HashBatchResult { results, cancelled }
+}
+
+/// Group files that are true duplicates of one another.
+///
+/// Runs the cheap partial hash over every path in parallel and buckets
+/// files whose partial hash collides. Singleton buckets can't be
+/// duplicates and are dropped without ever reading the rest of the file;
+/// the remaining buckets are re-hashed with [`HashMode::Full`] and
+/// re-grouped, so two files only end up in the same output group once an
+/// identical full-file hash confirms it. Files that fail to hash (missing,
+/// unreadable, ...) are silently excluded from every group.
+///
+/// Args:
+///     paths: List of file paths to compare
 ///
 /// Returns:
-///     List of dicts with path, hash (or None), and error (or None) for each file
+///     List of groups, where each group is the list of paths that are
+///     byte-identical to one another
 #[pyfunction]
-pub fn hash_files(paths: Vec<String>) -> Vec<FileHash> {
-    paths
+pub fn group_duplicates(paths: Vec<String>) -> Vec<Vec<String>> {
+    let partial_hashes: Vec<(String, String)> = paths
         .par_iter()
-        .map(|path| match compute_file_hash(path) {
-            Ok(hash) => FileHash {
-                path: path.clone(),
-                hash: Some(hash),
-                error: None,
-            },
-            Err(e) => FileHash {
-                path: path.clone(),
-                hash: None,
-                error: Some(e),
-            },
+        .filter_map(|path| {
+            compute_hash_with_mode(path, HashMode::Partial)
+                .ok()
+                .map(|hash| (path.clone(), hash))
+        })
+        .collect();
+
+    let mut partial_buckets: HashMap<String, Vec<String>> = HashMap::new();
+    for (path, hash) in partial_hashes {
+        partial_buckets.entry(hash).or_default().push(path);
+    }
+
+    partial_buckets
+        .into_values()
+        .filter(|bucket| bucket.len() > 1)
+        .flat_map(|bucket| {
+            let full_hashes: Vec<(String, String)> = bucket
+                .par_iter()
+                .filter_map(|path| {
+                    compute_hash_with_mode(path, HashMode::Full)
+                        .ok()
+                        .map(|hash| (path.clone(), hash))
+                })
+                .collect();
+
+            let mut full_buckets: HashMap<String, Vec<String>> = HashMap::new();
+            for (path, hash) in full_hashes {
+                full_buckets.entry(hash).or_default().push(path);
+            }
+
+            full_buckets
+                .into_values()
+                .filter(|group| group.len() > 1)
+                .collect::<Vec<_>>()
         })
         .collect()
 }
@@ -102,11 +262,12 @@ mod tests {
         let mut file = File::create(&path).unwrap();
         file.write_all(b"hello world").unwrap();
 
-        let result = hash_files(vec![path.to_string_lossy().to_string()]);
-        assert_eq!(result.len(), 1);
-        assert!(result[0].hash.is_some());
-        assert!(result[0].error.is_none());
-        assert!(result[0].hash.as_ref().unwrap().starts_with("xxh64:"));
+        let result = hash_files(vec![path.to_string_lossy().to_string()], None, None);
+        assert_eq!(result.results.len(), 1);
+        assert!(result.results[0].hash.is_some());
+        assert!(result.results[0].error.is_none());
+        assert!(result.results[0].hash.as_ref().unwrap().starts_with("xxh64:"));
+        assert!(!result.cancelled);
     }
 
     #[test]
@@ -118,22 +279,22 @@ mod tests {
         let data = vec![0u8; 200_000];
         file.write_all(&data).unwrap();
 
-        let result = hash_files(vec![path.to_string_lossy().to_string()]);
-        assert_eq!(result.len(), 1);
-        assert!(result[0].hash.is_some());
-        assert!(result[0].error.is_none());
+        let result = hash_files(vec![path.to_string_lossy().to_string()], None, None);
+        assert_eq!(result.results.len(), 1);
+        assert!(result.results[0].hash.is_some());
+        assert!(result.results[0].error.is_none());
 
-        let hash = result[0].hash.as_ref().unwrap();
+        let hash = result.results[0].hash.as_ref().unwrap();
         assert!(hash.starts_with("xxh64:"));
         assert!(hash.ends_with(":200000"));
     }
 
     #[test]
     fn test_hash_nonexistent_file() {
-        let result = hash_files(vec!["/nonexistent/file.bin".to_string()]);
-        assert_eq!(result.len(), 1);
-        assert!(result[0].hash.is_none());
-        assert!(result[0].error.is_some());
+        let result = hash_files(vec!["/nonexistent/file.bin".to_string()], None, None);
+        assert_eq!(result.results.len(), 1);
+        assert!(result.results[0].hash.is_none());
+        assert!(result.results[0].error.is_some());
     }
 
     #[test]
@@ -144,11 +305,120 @@ mod tests {
         File::create(&path1).unwrap().write_all(b"file1").unwrap();
         File::create(&path2).unwrap().write_all(b"file2").unwrap();
 
-        let result = hash_files(vec![
+        let result = hash_files(
+            vec![
+                path1.to_string_lossy().to_string(),
+                path2.to_string_lossy().to_string(),
+            ],
+            None,
+            None,
+        );
+        assert_eq!(result.results.len(), 2);
+        assert!(result.results.iter().all(|r| r.hash.is_some()));
+    }
+
+    #[test]
+    fn test_hash_files_cancelled_upfront_returns_no_results() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("small.bin");
+        File::create(&path).unwrap().write_all(b"hello").unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let result = hash_files_with_cancellation(
+            vec![path.to_string_lossy().to_string()],
+            None,
+            Some(token),
+        );
+        assert!(result.cancelled);
+        assert!(result.results.is_empty());
+    }
+
+    #[test]
+    fn test_group_duplicates_finds_identical_files() {
+        let dir = tempdir().unwrap();
+        let path1 = dir.path().join("a.bin");
+        let path2 = dir.path().join("b.bin");
+        let path3 = dir.path().join("c.bin");
+        File::create(&path1)
+            .unwrap()
+            .write_all(b"same contents")
+            .unwrap();
+        File::create(&path2)
+            .unwrap()
+            .write_all(b"same contents")
+            .unwrap();
+        File::create(&path3)
+            .unwrap()
+            .write_all(b"different")
+            .unwrap();
+
+        let groups = group_duplicates(vec![
             path1.to_string_lossy().to_string(),
             path2.to_string_lossy().to_string(),
+            path3.to_string_lossy().to_string(),
         ]);
-        assert_eq!(result.len(), 2);
-        assert!(result.iter().all(|r| r.hash.is_some()));
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+    }
+
+    #[test]
+    fn test_group_duplicates_no_matches() {
+        let dir = tempdir().unwrap();
+        let path1 = dir.path().join("a.bin");
+        let path2 = dir.path().join("b.bin");
+        File::create(&path1).unwrap().write_all(b"one").unwrap();
+        File::create(&path2).unwrap().write_all(b"two").unwrap();
+
+        let groups = group_duplicates(vec![
+            path1.to_string_lossy().to_string(),
+            path2.to_string_lossy().to_string(),
+        ]);
+
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn test_group_duplicates_rejects_partial_hash_collision_with_different_middle() {
+        // Two files >= 128KB with identical first 64KB, identical last
+        // 64KB, and identical size collide on compute_partial_file_hash,
+        // which only looks at those chunks. They must still be told apart
+        // by the full-hash re-verification stage if their middles differ.
+        let dir = tempdir().unwrap();
+        let path1 = dir.path().join("a.bin");
+        let path2 = dir.path().join("b.bin");
+
+        let first_chunk = vec![0xAAu8; CHUNK_SIZE];
+        let last_chunk = vec![0xBBu8; CHUNK_SIZE];
+        let mut middle1 = vec![0x11u8; CHUNK_SIZE];
+        let mut middle2 = vec![0x22u8; CHUNK_SIZE];
+        middle1[0] = 0x01;
+        middle2[0] = 0x02;
+
+        let mut contents1 = first_chunk.clone();
+        contents1.extend_from_slice(&middle1);
+        contents1.extend_from_slice(&last_chunk);
+
+        let mut contents2 = first_chunk;
+        contents2.extend_from_slice(&middle2);
+        contents2.extend_from_slice(&last_chunk);
+
+        assert_eq!(contents1.len(), contents2.len());
+        File::create(&path1).unwrap().write_all(&contents1).unwrap();
+        File::create(&path2).unwrap().write_all(&contents2).unwrap();
+
+        assert_eq!(
+            compute_partial_file_hash(&path1.to_string_lossy()).unwrap(),
+            compute_partial_file_hash(&path2.to_string_lossy()).unwrap(),
+        );
+
+        let groups = group_duplicates(vec![
+            path1.to_string_lossy().to_string(),
+            path2.to_string_lossy().to_string(),
+        ]);
+
+        assert!(groups.iter().all(|group| group.len() < 2));
     }
 }
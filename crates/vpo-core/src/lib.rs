@@ -1,7 +1,10 @@
 use pyo3::prelude::*;
 
+mod cache;
 mod discovery;
 mod hasher;
+mod progress;
+mod similarity;
 
 /// Returns the version of the vpo-core library.
 #[pyfunction]
@@ -16,5 +19,9 @@ fn vpo_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(version, m)?)?;
     m.add_function(wrap_pyfunction!(discovery::discover_videos, m)?)?;
     m.add_function(wrap_pyfunction!(hasher::hash_files, m)?)?;
+    m.add_function(wrap_pyfunction!(hasher::group_duplicates, m)?)?;
+    m.add_function(wrap_pyfunction!(similarity::find_similar_videos, m)?)?;
+    m.add_function(wrap_pyfunction!(cache::hash_files_cached, m)?)?;
+    m.add_class::<progress::CancellationToken>()?;
     Ok(())
 }
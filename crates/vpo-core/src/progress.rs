@@ -0,0 +1,76 @@
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How many items pass between progress callback invocations. Throttling
+/// this way keeps a multi-million-file scan from contending on the GIL
+/// once per file.
+const PROGRESS_THROTTLE: usize = 50;
+
+/// A cancellation flag shared between Python and a running scan or hash
+/// pass.
+///
+/// Python code creates one, hands it to `discover_videos`/`hash_files` as
+/// `cancel_token`, and calls `cancel()` (from another thread, or a signal
+/// handler) to stop the pass early. The pass polls
+/// [`CancellationToken::is_set`] between items and returns whatever it has
+/// collected so far instead of running to completion.
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    flag: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl CancellationToken {
+    #[new]
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.is_set()
+    }
+}
+
+impl CancellationToken {
+    pub(crate) fn is_set(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
+
+/// Throttled progress reporting back into Python.
+///
+/// Call [`ProgressReporter::tick`] once per item processed; it invokes the
+/// callback with the running count, but at most once every
+/// `PROGRESS_THROTTLE` items.
+pub(crate) struct ProgressReporter {
+    callback: Option<PyObject>,
+    count: AtomicUsize,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(callback: Option<PyObject>) -> Self {
+        ProgressReporter {
+            callback,
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn tick(&self) {
+        let seen = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        if !seen.is_multiple_of(PROGRESS_THROTTLE) {
+            return;
+        }
+        Python::with_gil(|py| {
+            let _ = callback.call1(py, (seen,));
+        });
+    }
+}
@@ -0,0 +1,437 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+
+/// Number of evenly spaced frames sampled per video.
+const FRAME_COUNT: usize = 5;
+/// Side length of the grayscale grid each frame is downscaled to.
+const GRID_SIZE: usize = 32;
+/// Bits contributed by a single frame (one per grid cell).
+const BITS_PER_FRAME: usize = GRID_SIZE * GRID_SIZE;
+
+/// A perceptual hash: `FRAME_COUNT * BITS_PER_FRAME` bits packed into `u64` words.
+type PerceptualHash = Vec<u64>;
+
+/// Hamming distance between two equal-length perceptual hashes, in bits.
+fn hamming_distance(a: &PerceptualHash, b: &PerceptualHash) -> u32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+/// A node in a [BK-tree](https://en.wikipedia.org/wiki/BK-tree) keyed on Hamming
+/// distance. Hamming distance obeys the triangle inequality, which is what
+/// makes it a valid BK-tree metric: any two hashes within `tolerance` of each
+/// other can only be reached by recursing into children whose edge distance
+/// falls in `[d - tolerance, d + tolerance]`.
+struct BkNode {
+    path: String,
+    hash: PerceptualHash,
+    children: HashMap<u32, BkNode>,
+}
+
+#[derive(Default)]
+struct BkTree {
+    root: Option<BkNode>,
+}
+
+impl BkTree {
+    fn new() -> Self {
+        BkTree::default()
+    }
+
+    fn insert(&mut self, path: String, hash: PerceptualHash) {
+        let Some(root) = &mut self.root else {
+            self.root = Some(BkNode {
+                path,
+                hash,
+                children: HashMap::new(),
+            });
+            return;
+        };
+
+        let mut node = root;
+        loop {
+            let distance = hamming_distance(&node.hash, &hash);
+            node = node.children.entry(distance).or_insert_with(|| BkNode {
+                path: path.clone(),
+                hash: hash.clone(),
+                children: HashMap::new(),
+            });
+            if node.path == path {
+                return;
+            }
+        }
+    }
+
+    /// All paths within `tolerance` bits of `hash`, paired with their distance.
+    fn query(&self, hash: &PerceptualHash, tolerance: u32) -> Vec<(String, u32)> {
+        let mut matches = Vec::new();
+        if let Some(root) = &self.root {
+            Self::query_node(root, hash, tolerance, &mut matches);
+        }
+        matches
+    }
+
+    fn query_node(node: &BkNode, hash: &PerceptualHash, tolerance: u32, matches: &mut Vec<(String, u32)>) {
+        let distance = hamming_distance(&node.hash, hash);
+        if distance <= tolerance {
+            matches.push((node.path.clone(), distance));
+        }
+
+        let low = distance.saturating_sub(tolerance);
+        let high = distance + tolerance;
+        for (edge, child) in &node.children {
+            if *edge >= low && *edge <= high {
+                Self::query_node(child, hash, tolerance, matches);
+            }
+        }
+    }
+}
+
+/// Pairwise distance between two clustered videos, surfaced to Python.
+#[derive(Clone)]
+pub struct PairDistance {
+    pub a: String,
+    pub b: String,
+    pub distance: u32,
+}
+
+impl IntoPy<PyObject> for PairDistance {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("a", self.a).unwrap();
+        dict.set_item("b", self.b).unwrap();
+        dict.set_item("distance", self.distance).unwrap();
+        dict.into()
+    }
+}
+
+/// A cluster of videos linked by chains of pairs within `tolerance` bits of
+/// each other; two members of one cluster may themselves be further apart.
+/// `pairwise_distances` holds only the member pairs within `tolerance`.
+#[derive(Clone)]
+pub struct SimilarityCluster {
+    pub paths: Vec<String>,
+    pub pairwise_distances: Vec<PairDistance>,
+}
+
+impl IntoPy<PyObject> for SimilarityCluster {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("paths", self.paths).unwrap();
+        dict.set_item("pairwise_distances", self.pairwise_distances.into_py(py))
+            .unwrap();
+        dict.into()
+    }
+}
+
+/// Outcome of a [`find_similar_videos`] pass: the clusters found, plus the
+/// `(path, error)` pairs for any video that couldn't be perceptually
+/// hashed (corrupt file, unreadable, missing decoder) and so was left out
+/// of every cluster.
+#[derive(Clone)]
+pub struct SimilarityResult {
+    pub clusters: Vec<SimilarityCluster>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl IntoPy<PyObject> for SimilarityResult {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("clusters", self.clusters.into_py(py)).unwrap();
+        dict.set_item("failed", self.failed).unwrap();
+        dict.into()
+    }
+}
+
+/// Probe a video's duration in seconds via `ffprobe`.
+fn probe_duration_secs(path: &str) -> Result<f64, String> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+            path,
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| describe_missing_decoder("ffprobe", &e))?;
+
+    if !output.status.success() {
+        return Err(format!("ffprobe failed to read duration for {}", path));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("ffprobe returned no duration for {}", path))
+}
+
+/// Decode a single frame at `timestamp_secs` into a `GRID_SIZE x GRID_SIZE`
+/// grayscale pixel grid via `ffmpeg`.
+fn extract_frame_grid(path: &str, timestamp_secs: f64) -> Result<Vec<u8>, String> {
+    let output = Command::new("ffmpeg")
+        .args([
+            "-v",
+            "quiet",
+            "-ss",
+            &format!("{:.3}", timestamp_secs),
+            "-i",
+            path,
+            "-vframes",
+            "1",
+            "-vf",
+            &format!("scale={}:{}", GRID_SIZE, GRID_SIZE),
+            "-pix_fmt",
+            "gray",
+            "-f",
+            "rawvideo",
+            "-",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| describe_missing_decoder("ffmpeg", &e))?;
+
+    if output.stdout.len() != BITS_PER_FRAME {
+        return Err(format!(
+            "ffmpeg produced {} bytes for {} at {:.3}s, expected {}",
+            output.stdout.len(),
+            path,
+            timestamp_secs,
+            BITS_PER_FRAME
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+fn describe_missing_decoder(binary: &str, error: &std::io::Error) -> String {
+    if error.kind() == std::io::ErrorKind::NotFound {
+        format!("{} not found: install it and ensure it is on PATH", binary)
+    } else {
+        format!("failed to run {}: {}", binary, error)
+    }
+}
+
+/// Average hash: each grid cell becomes a `1` bit if it is at or above the
+/// frame's mean brightness, `0` otherwise.
+fn average_hash_bits(grid: &[u8]) -> Vec<bool> {
+    let mean = grid.iter().map(|&b| b as u64).sum::<u64>() / grid.len() as u64;
+    grid.iter().map(|&b| b as u64 >= mean).collect()
+}
+
+fn pack_bits(bits: impl Iterator<Item = bool>) -> PerceptualHash {
+    let mut words = Vec::new();
+    let mut current = 0u64;
+    let mut count = 0;
+    for bit in bits {
+        if bit {
+            current |= 1 << count;
+        }
+        count += 1;
+        if count == 64 {
+            words.push(current);
+            current = 0;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        words.push(current);
+    }
+    words
+}
+
+/// Compute a fixed-length perceptual hash for a video: decode `FRAME_COUNT`
+/// evenly spaced frames, downscale each to a `GRID_SIZE x GRID_SIZE` grayscale
+/// grid, average-hash each frame, and concatenate the bits.
+fn compute_perceptual_hash(path: &str) -> Result<PerceptualHash, String> {
+    let duration = probe_duration_secs(path)?;
+    let mut bits = Vec::with_capacity(FRAME_COUNT * BITS_PER_FRAME);
+
+    for i in 0..FRAME_COUNT {
+        // Sample evenly spaced timestamps, avoiding the very first/last frame
+        // where black bars or logos are more common.
+        let timestamp = duration * (i as f64 + 1.0) / (FRAME_COUNT as f64 + 1.0);
+        let grid = extract_frame_grid(path, timestamp)?;
+        bits.extend(average_hash_bits(&grid));
+    }
+
+    Ok(pack_bits(bits.into_iter()))
+}
+
+/// A small disjoint-set forest used to union videos into similarity clusters.
+struct UnionFind {
+    parent: HashMap<String, String>,
+}
+
+impl UnionFind {
+    fn new(paths: impl Iterator<Item = String>) -> Self {
+        UnionFind {
+            parent: paths.map(|p| (p.clone(), p)).collect(),
+        }
+    }
+
+    fn find(&mut self, path: &str) -> String {
+        let parent = self.parent.get(path).cloned().unwrap_or_else(|| path.to_string());
+        if parent == path {
+            return parent;
+        }
+        let root = self.find(&parent);
+        self.parent.insert(path.to_string(), root.clone());
+        root
+    }
+
+    fn union(&mut self, a: &str, b: &str) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parent.insert(root_a, root_b);
+        }
+    }
+}
+
+/// Find near-duplicate videos: the same movie re-encoded at a different
+/// bitrate or resolution, rather than byte-identical files.
+///
+/// Args:
+///     paths: List of video file paths to compare
+///     tolerance: Maximum Hamming distance (in bits) for two videos to be
+///         considered similar
+///
+/// Returns:
+///     A dict with `clusters` (each holding the grouped paths plus the
+///     distances of its member pairs within `tolerance`) and `failed` (the `(path, error)`
+///     pairs for any video that couldn't be hashed)
+#[pyfunction]
+pub fn find_similar_videos(paths: Vec<String>, tolerance: u32) -> PyResult<SimilarityResult> {
+    let results: Vec<Result<(String, PerceptualHash), (String, String)>> = paths
+        .par_iter()
+        .map(|path| {
+            compute_perceptual_hash(path)
+                .map(|hash| (path.clone(), hash))
+                .map_err(|e| (path.clone(), e))
+        })
+        .collect();
+
+    let mut hashed: Vec<(String, PerceptualHash)> = Vec::new();
+    let mut failed: Vec<(String, String)> = Vec::new();
+    for result in results {
+        match result {
+            Ok(entry) => hashed.push(entry),
+            Err(entry) => failed.push(entry),
+        }
+    }
+
+    if hashed.is_empty() {
+        if let Some((path, error)) = failed.into_iter().next() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "{}: {}",
+                path, error
+            )));
+        }
+        return Ok(SimilarityResult {
+            clusters: Vec::new(),
+            failed: Vec::new(),
+        });
+    }
+
+    let mut tree = BkTree::new();
+    for (path, hash) in &hashed {
+        tree.insert(path.clone(), hash.clone());
+    }
+
+    let mut union_find = UnionFind::new(hashed.iter().map(|(path, _)| path.clone()));
+    let mut pairwise: Vec<PairDistance> = Vec::new();
+
+    for (path, hash) in &hashed {
+        for (neighbour, distance) in tree.query(hash, tolerance) {
+            if neighbour == *path {
+                continue;
+            }
+            union_find.union(path, &neighbour);
+            if path.as_str() < neighbour.as_str() {
+                pairwise.push(PairDistance {
+                    a: path.clone(),
+                    b: neighbour,
+                    distance,
+                });
+            }
+        }
+    }
+
+    let mut clusters: HashMap<String, Vec<String>> = HashMap::new();
+    for (path, _) in &hashed {
+        let root = union_find.find(path);
+        clusters.entry(root).or_default().push(path.clone());
+    }
+
+    let clusters = clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let member_set: std::collections::HashSet<&String> = members.iter().collect();
+            let pairwise_distances = pairwise
+                .iter()
+                .filter(|pd| member_set.contains(&pd.a) && member_set.contains(&pd.b))
+                .cloned()
+                .collect();
+            SimilarityCluster {
+                paths: members,
+                pairwise_distances,
+            }
+        })
+        .collect();
+
+    Ok(SimilarityResult { clusters, failed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hamming_distance_counts_differing_bits() {
+        let a = vec![0b1010u64];
+        let b = vec![0b1000u64];
+        assert_eq!(hamming_distance(&a, &b), 1);
+    }
+
+    #[test]
+    fn test_hamming_distance_identical_is_zero() {
+        let a = vec![u64::MAX, 0, 7];
+        assert_eq!(hamming_distance(&a, &a.clone()), 0);
+    }
+
+    #[test]
+    fn test_bk_tree_query_within_tolerance() {
+        let mut tree = BkTree::new();
+        tree.insert("near.mkv".to_string(), vec![0b0000]);
+        tree.insert("far.mkv".to_string(), vec![0b1111]);
+
+        let matches = tree.query(&vec![0b0001], 1);
+        let paths: Vec<&str> = matches.iter().map(|(p, _)| p.as_str()).collect();
+        assert!(paths.contains(&"near.mkv"));
+        assert!(!paths.contains(&"far.mkv"));
+    }
+
+    #[test]
+    fn test_union_find_merges_transitively() {
+        let mut uf = UnionFind::new(["a", "b", "c"].iter().map(|s| s.to_string()));
+        uf.union("a", "b");
+        uf.union("b", "c");
+        assert_eq!(uf.find("a"), uf.find("c"));
+    }
+
+    #[test]
+    fn test_average_hash_bits_splits_on_mean() {
+        let grid = vec![0u8, 255];
+        let bits = average_hash_bits(&grid);
+        assert_eq!(bits, vec![false, true]);
+    }
+}